@@ -1,6 +1,14 @@
 use anyhow::Result;
+use futures_util::stream::{select_all, Stream};
+use futures_util::StreamExt;
+use std::collections::HashSet;
 use std::num::ParseIntError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 use thiserror::Error;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+use x11rb::rust_connection::RustConnection;
 use zbus::{dbus_proxy, Connection};
 
 #[derive(Error, Debug)]
@@ -9,6 +17,18 @@ pub enum EvalError {
     CountParseError(#[from] ParseIntError),
     #[error("DBUS connection failure")]
     DBusConnectionError(#[from] zbus::Error),
+    #[error("Malformed batch response row: {0}")]
+    BatchParseError(String),
+    #[error("Lua runtime error: {0}")]
+    LuaRuntimeError(String),
+    #[error("X11 error: {0}")]
+    X11(String),
+    #[error("Node is stale: AwesomeWM reports it no longer exists")]
+    StaleNode,
+}
+
+fn x11_err(err: impl std::fmt::Display) -> EvalError {
+    return EvalError::X11(err.to_string());
 }
 
 #[dbus_proxy(
@@ -20,9 +40,139 @@ trait Commander {
     async fn eval(&self, code: &str) -> Result<String, EvalError>;
 }
 
-#[derive(Debug)]
+/// The signal side of the bridge: `EVENTS_HOOK_SCRIPT` makes AwesomeWM emit
+/// these over D-Bus, and `Awesome::events` subscribes to them instead of
+/// callers polling `get_full_tree`/`get_clients` for changes.
+#[dbus_proxy(
+    interface = "org.awesomewm.awful.Events",
+    default_service = "org.awesomewm.awful",
+    default_path = "/"
+)]
+trait Events {
+    // `EVENTS_HOOK_SCRIPT` emits these member names verbatim via Lua's
+    // `dbus.emit_signal`; override the macro's default PascalCase
+    // conversion so the emitter and receiver agree on the wire name.
+    #[dbus_proxy(signal, name = "client_managed")]
+    fn client_managed(&self, window: u32, class: String) -> zbus::Result<()>;
+    #[dbus_proxy(signal, name = "client_unmanaged")]
+    fn client_unmanaged(&self, window: u32) -> zbus::Result<()>;
+    #[dbus_proxy(signal, name = "client_focused")]
+    fn client_focused(&self, window: u32) -> zbus::Result<()>;
+    #[dbus_proxy(signal, name = "tag_selected")]
+    fn tag_selected(&self, screen: u32, index: u32, selected: bool) -> zbus::Result<()>;
+}
+
+/// A Lua value that can be safely spliced into a generated expression.
+///
+/// `Int` and `Str` are rendered through an escaping encoder, so callers
+/// never hand-splice strings (tag names, spawn commands, ...) into Lua
+/// source themselves. `Raw` accepts a pre-built Lua expression verbatim,
+/// for cases like passing a `LuaPath` as an argument to another call.
+#[derive(Debug, Clone)]
+pub enum LuaVal {
+    Int(i64),
+    Str(String),
+    Raw(String),
+}
+
+impl LuaVal {
+    fn render(&self) -> String {
+        match self {
+            LuaVal::Int(n) => n.to_string(),
+            LuaVal::Str(s) => quote_lua_string(s),
+            LuaVal::Raw(expr) => expr.clone(),
+        }
+    }
+}
+
+/// Quotes `s` as a Lua long-bracket string (`[[...]]`, widening to
+/// `[=[...]=]` etc. as needed), which needs no escaping of quotes or
+/// backslashes and can't be broken out of by embedding `"` or `'`.
+fn quote_lua_string(s: &str) -> String {
+    let mut level = 0;
+    loop {
+        let closing = format!("]{}]", "=".repeat(level));
+        // Check `s` plus a trailing `]`, so a body ending in `]`/`]=`/...
+        // can't combine with the closing bracket into an earlier match.
+        if !format!("{s}]").contains(&closing) {
+            break;
+        }
+        level += 1;
+    }
+    let eq = "=".repeat(level);
+    // A long bracket that opens right before a newline eats that first
+    // newline, so start with one to guarantee `s` is reproduced verbatim.
+    format!("[{eq}[\n{s}]{eq}]")
+}
+
+/// Builds the `awful.spawn(...)` expression for `cmd`, quoting it through
+/// `LuaVal::Str` so a command containing quotes can't break out of the
+/// call. Factored out of `Awesome::spawn` so the quoting is unit-testable
+/// without a live D-Bus connection.
+fn spawn_expr(cmd: &str) -> String {
+    return format!("awful.spawn({})", LuaVal::Str(cmd.to_string()).render());
+}
+
+/// Builds `screen[n].tags[n]:clients()[n]` style Lua paths with the
+/// 0-based -> 1-based index conversion applied in exactly one place,
+/// rather than at every call site.
+#[derive(Debug, Clone)]
+pub struct LuaPath {
+    expr: String,
+}
+
+impl LuaPath {
+    pub fn screen(index: u32) -> Self {
+        return LuaPath {
+            expr: format!("screen[{}]", index + 1),
+        };
+    }
+
+    pub fn tag(mut self, index: u32) -> Self {
+        self.expr = format!("{}.tags[{}]", self.expr, index + 1);
+        return self;
+    }
+
+    pub fn client(mut self, index: u32) -> Self {
+        self.expr = format!("{}:clients()[{}]", self.expr, index + 1);
+        return self;
+    }
+
+    pub fn field(mut self, name: &str) -> Self {
+        self.expr = format!("{}.{}", self.expr, name);
+        return self;
+    }
+
+    pub fn call(mut self, method: &str, args: &[LuaVal]) -> Self {
+        let rendered: Vec<String> = args.iter().map(LuaVal::render).collect();
+        self.expr = format!("{}:{}({})", self.expr, method, rendered.join(", "));
+        return self;
+    }
+
+    /// Wraps the path in Lua's length operator (`#expr`).
+    pub fn count(self) -> String {
+        return format!("#{}", self.expr);
+    }
+
+    pub fn into_expr(self) -> String {
+        return self.expr;
+    }
+}
+
 pub struct Awesome<'a> {
     proxy: CommanderProxy<'a>,
+    connection: Connection,
+    /// Direct X11 connection, behind the XID AwesomeWM already hands us.
+    /// Lazily initialized so D-Bus-only workflows never pay for it. `Arc`'d
+    /// so callers can move a handle into `spawn_blocking` rather than
+    /// blocking the async executor on the connection's blocking replies.
+    x11: OnceLock<Arc<RustConnection>>,
+}
+
+impl std::fmt::Debug for Awesome<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.debug_struct("Awesome").finish_non_exhaustive();
+    }
 }
 
 #[derive(Debug)]
@@ -46,13 +196,75 @@ pub struct AwesomeClient<'a> {
 impl Awesome<'_> {
     pub async fn new(connection: &Connection) -> Result<Awesome, EvalError> {
         let proxy = CommanderProxy::new(connection).await?;
-        return Ok(Awesome { proxy });
+        return Ok(Awesome {
+            proxy,
+            connection: connection.clone(),
+            x11: OnceLock::new(),
+        });
+    }
+
+    /// Returns the lazily-initialized X11 connection, opening it on first
+    /// use. Concurrent first calls may each open a connection; only the
+    /// first to finish is kept and the rest are dropped. Returns a cloned
+    /// `Arc` handle (not a borrow) so callers can move it into
+    /// `spawn_blocking`.
+    fn x11(&self) -> Result<Arc<RustConnection>, EvalError> {
+        if let Some(conn) = self.x11.get() {
+            return Ok(conn.clone());
+        }
+        let (conn, _screen) = x11rb::connect(None).map_err(x11_err)?;
+        let _ = self.x11.set(Arc::new(conn));
+        return Ok(self.x11.get().expect("just set").clone());
     }
     async fn execute(&self, code: &str) -> Result<String, EvalError> {
         let formatted_query = format!("return tostring({})", code);
         return self.proxy.eval(&formatted_query).await;
     }
 
+    /// Like `execute`, but the Lua source supplies its own `return` (and
+    /// is trusted to already serialize to a string). Used by `BatchQuery`,
+    /// whose generated scripts are full programs rather than bare
+    /// expressions.
+    async fn execute_script(&self, script: &str) -> Result<String, EvalError> {
+        return self.proxy.eval(script).await;
+    }
+
+    /// Starts a batched query: expressions pushed onto the returned
+    /// `BatchQuery` are all resolved with a single `eval` round-trip
+    /// instead of one per expression.
+    pub fn batch(&self) -> BatchQuery {
+        return BatchQuery::new(self);
+    }
+
+    /// Starts a `ScreenRegistry`: an in-memory snapshot of the screen/tag/
+    /// client tree, served from memory until an explicit `refresh()`
+    /// rather than re-querying D-Bus on every navigation.
+    pub fn registry(&self) -> ScreenRegistry {
+        return ScreenRegistry::new(self);
+    }
+
+    /// Like `execute`, but for Lua statements run for effect rather than
+    /// for their return value (moving clients, spawning processes, ...).
+    /// The statement is run inside a `pcall`, so a Lua runtime error comes
+    /// back as `Err` instead of being silently treated as success.
+    async fn execute_void(&self, code: &str) -> Result<(), EvalError> {
+        let script = format!(
+            "local ok, err = pcall(function() {} end) if not ok then return tostring(err) else return \"\" end",
+            code
+        );
+        let result = self.execute_script(&script).await?;
+
+        if result.is_empty() {
+            return Ok(());
+        }
+        return Err(EvalError::LuaRuntimeError(result));
+    }
+
+    /// Spawns `cmd` via `awful.spawn`.
+    pub async fn spawn(&self, cmd: &str) -> Result<(), EvalError> {
+        return self.execute_void(&spawn_expr(cmd)).await;
+    }
+
     pub async fn get_screen_count(&self) -> Result<u32, EvalError> {
         let screen_count = self.execute("screen:count()").await?;
         return screen_count
@@ -71,13 +283,160 @@ impl Awesome<'_> {
 
         return Ok(screens);
     }
+
+    /// Fetches the full screen/tag/client hierarchy in a single `eval`
+    /// round-trip, instead of the one-call-per-property walk that
+    /// `get_screens`/`get_tags`/`get_clients` require. The returned tree
+    /// is a snapshot: entries that disappeared between enumeration and
+    /// read inside AwesomeWM show up as `None` fields rather than
+    /// failing the whole batch.
+    ///
+    /// `FULL_TREE_EXPR` always returns a string, even for zero screens
+    /// (`table.concat` of an empty row list is `""`), so `resolve()`
+    /// reporting `None` here means the whole expression raised a Lua
+    /// error or returned `nil`, not "empty desktop" — that's surfaced as
+    /// an error rather than silently parsed as zero screens.
+    pub async fn get_full_tree(&self) -> Result<Vec<FullScreen>, EvalError> {
+        let mut batch = self.batch();
+        batch.push(FULL_TREE_EXPR);
+
+        let mut fields = batch.resolve().await?;
+        let raw = fields
+            .pop()
+            .flatten()
+            .ok_or_else(|| EvalError::LuaRuntimeError("FULL_TREE_EXPR raised an error or returned nil".to_string()))?;
+
+        return parse_full_tree(&raw);
+    }
+
+    /// Subscribes to AwesomeWM client/tag changes instead of having to
+    /// poll `get_full_tree`. Injects `EVENTS_HOOK_SCRIPT` (idempotent: a
+    /// `_G` guard keeps repeated calls, including after this method is
+    /// called more than once, from hooking the same signal twice) and
+    /// resolves to a stream merging every event kind AwesomeWM reports.
+    ///
+    /// An AwesomeWM restart reloads its Lua state and clears the `_G`
+    /// guard along with it, so the returned stream also watches
+    /// `org.awesomewm.awful`'s bus ownership via `NameOwnerChanged` and
+    /// re-runs `EVENTS_HOOK_SCRIPT` whenever it regains its name, instead
+    /// of leaving that to the caller to notice and re-call `events()`.
+    pub async fn events(&self) -> Result<impl Stream<Item = AwesomeEvent> + '_, EvalError> {
+        self.execute_void(EVENTS_HOOK_SCRIPT).await?;
+
+        let events = EventsProxy::new(&self.connection).await?;
+        let dbus = zbus::fdo::DBusProxy::new(&self.connection).await?;
+
+        let restarts = dbus
+            .receive_name_owner_changed()
+            .await?
+            .filter_map(move |signal| async move {
+                if let Ok(args) = signal.args() {
+                    let regained_owner = args.new_owner().as_ref().is_some_and(|owner| !owner.is_empty());
+                    if args.name() == "org.awesomewm.awful" && regained_owner {
+                        let _ = self.execute_void(EVENTS_HOOK_SCRIPT).await;
+                    }
+                }
+                None::<AwesomeEvent>
+            })
+            .boxed();
+
+        let client_managed = events
+            .receive_client_managed()
+            .await?
+            .filter_map(|signal| async move {
+                signal.args().ok().map(|args| AwesomeEvent::ClientManaged {
+                    window: args.window,
+                    class: args.class,
+                })
+            })
+            .boxed();
+
+        let client_unmanaged = events
+            .receive_client_unmanaged()
+            .await?
+            .filter_map(|signal| async move {
+                signal
+                    .args()
+                    .ok()
+                    .map(|args| AwesomeEvent::ClientUnmanaged { window: args.window })
+            })
+            .boxed();
+
+        let client_focused = events
+            .receive_client_focused()
+            .await?
+            .filter_map(|signal| async move {
+                signal
+                    .args()
+                    .ok()
+                    .map(|args| AwesomeEvent::ClientFocused { window: args.window })
+            })
+            .boxed();
+
+        let tag_selected = events
+            .receive_tag_selected()
+            .await?
+            .filter_map(|signal| async move {
+                signal.args().ok().map(|args| AwesomeEvent::TagSelected {
+                    screen: args.screen,
+                    index: args.index,
+                    selected: args.selected,
+                })
+            })
+            .boxed();
+
+        return Ok(select_all(vec![
+            restarts,
+            client_managed,
+            client_unmanaged,
+            client_focused,
+            tag_selected,
+        ]));
+    }
+}
+
+/// Events reported by the hook installed by `EVENTS_HOOK_SCRIPT`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AwesomeEvent {
+    ClientManaged { window: u32, class: String },
+    ClientUnmanaged { window: u32 },
+    ClientFocused { window: u32 },
+    TagSelected { screen: u32, index: u32, selected: bool },
 }
 
+/// Hooks `client`/`tag` signals once per AwesomeWM Lua state and re-emits
+/// them as D-Bus signals on `org.awesomewm.awful.Events`, via Awesome's
+/// built-in `dbus` Lua module, so `Awesome::events` can subscribe instead
+/// of polling. Guarded by a `_G` flag so re-running this (e.g. a second
+/// call to `events()`) doesn't register the same signal handler twice.
+const EVENTS_HOOK_SCRIPT: &str = r#"
+if not _G.__awmrandr_events_hooked then
+    _G.__awmrandr_events_hooked = true
+
+    local function emit(name, ...)
+        dbus.emit_signal("session", "/", "org.awesomewm.awful.Events", name, ...)
+    end
+
+    client.connect_signal("manage", function(c)
+        emit("client_managed", c.window, c.class or "")
+    end)
+    client.connect_signal("unmanage", function(c)
+        emit("client_unmanaged", c.window)
+    end)
+    client.connect_signal("focus", function(c)
+        emit("client_focused", c.window)
+    end)
+    tag.connect_signal("property::selected", function(t)
+        emit("tag_selected", t.screen.index, t.index, t.selected)
+    end)
+end
+"#;
+
 impl AwesomeScreen<'_> {
     pub async fn get_tag_count(&self) -> Result<u32, EvalError> {
         let tag_count = self
             .instance
-            .execute(&format!("#screen[{}].tags", self.index + 1))
+            .execute(&LuaPath::screen(self.index).field("tags").count())
             .await?;
         return tag_count
             .parse()
@@ -102,11 +461,7 @@ impl AwesomeTag<'_> {
         let tag_name = self
             .screen
             .instance
-            .execute(&format!(
-                "screen[{}].tags[{}].name",
-                self.screen.index + 1,
-                self.index + 1
-            ))
+            .execute(&self.path().field("name").into_expr())
             .await?;
         return Ok(tag_name);
     }
@@ -114,11 +469,7 @@ impl AwesomeTag<'_> {
         let client_count = self
             .screen
             .instance
-            .execute(&format!(
-                "#screen[{}].tags[{}]:clients()",
-                self.screen.index + 1,
-                self.index + 1
-            ))
+            .execute(&self.path().call("clients", &[]).count())
             .await?;
         let client_count = client_count
             .parse()
@@ -127,11 +478,33 @@ impl AwesomeTag<'_> {
         let mut clients = Vec::new();
 
         for i in 0..client_count {
-            clients.push(AwesomeClient { index: i + 1, tag: self });
+            clients.push(AwesomeClient { index: i, tag: self });
         }
 
         return Ok(clients);
     }
+
+    /// Selects this tag exclusively (`t:view_only()`).
+    pub async fn view_only(&self) -> Result<(), EvalError> {
+        return self
+            .screen
+            .instance
+            .execute_void(&self.path().call("view_only", &[]).into_expr())
+            .await;
+    }
+
+    /// Toggles this tag's selected state (`t:toggle()`).
+    pub async fn toggle(&self) -> Result<(), EvalError> {
+        return self
+            .screen
+            .instance
+            .execute_void(&self.path().call("toggle", &[]).into_expr())
+            .await;
+    }
+
+    fn path(&self) -> LuaPath {
+        return LuaPath::screen(self.screen.index).tag(self.index);
+    }
 }
 
 impl AwesomeClient<'_> {
@@ -140,27 +513,17 @@ impl AwesomeClient<'_> {
             .tag
             .screen
             .instance
-            .execute(&format!(
-                "screen[{}].tags[{}]:clients()[{}].name",
-                self.tag.screen.index + 1,
-                self.tag.index + 1,
-                self.index
-            ))
+            .execute(&self.path().field("name").into_expr())
             .await?;
         return Ok(client_name);
     }
-    
+
     pub async fn get_x_window_id(&self) -> Result<u32, EvalError> {
         let client_name = self
             .tag
             .screen
             .instance
-            .execute(&format!(
-                "screen[{}].tags[{}]:clients()[{}].window",
-                self.tag.screen.index + 1,
-                self.tag.index + 1,
-                self.index
-            ))
+            .execute(&self.path().field("window").into_expr())
             .await?;
         return client_name
             .parse()
@@ -172,13 +535,720 @@ impl AwesomeClient<'_> {
             .tag
             .screen
             .instance
-            .execute(&format!(
-                "screen[{}].tags[{}]:clients()[{}].class",
-                self.tag.screen.index + 1,
-                self.tag.index + 1,
-                self.index
-            ))
+            .execute(&self.path().field("class").into_expr())
             .await?;
         return Ok(client_name);
     }
+
+    /// Moves this client to `tag` (`c:move_to_tag(t)`).
+    pub async fn move_to_tag(&self, tag: &AwesomeTag<'_>) -> Result<(), EvalError> {
+        let target = LuaPath::screen(tag.screen.index).tag(tag.index).into_expr();
+        return self
+            .tag
+            .screen
+            .instance
+            .execute_void(
+                &self
+                    .path()
+                    .call("move_to_tag", &[LuaVal::Raw(target)])
+                    .into_expr(),
+            )
+            .await;
+    }
+
+    /// Focuses this client (`client.focus = c`).
+    pub async fn set_focus(&self) -> Result<(), EvalError> {
+        return self
+            .tag
+            .screen
+            .instance
+            .execute_void(&format!("client.focus = {}", self.path().into_expr()))
+            .await;
+    }
+
+    /// Toggles this client's floating state (`c.floating = not c.floating`).
+    pub async fn toggle_floating(&self) -> Result<(), EvalError> {
+        return self
+            .tag
+            .screen
+            .instance
+            .execute_void(&format!(
+                "local c = {} c.floating = not c.floating",
+                self.path().into_expr()
+            ))
+            .await;
+    }
+
+    /// Closes this client (`c:kill()`).
+    pub async fn kill(&self) -> Result<(), EvalError> {
+        return self
+            .tag
+            .screen
+            .instance
+            .execute_void(&self.path().call("kill", &[]).into_expr())
+            .await;
+    }
+
+    fn path(&self) -> LuaPath {
+        return LuaPath::screen(self.tag.screen.index)
+            .tag(self.tag.index)
+            .client(self.index);
+    }
+
+    /// Pixel-accurate frame geometry, read directly from X11 rather than
+    /// through the eval bridge (AwesomeWM's Lua-side rounding can lose
+    /// precision `GetGeometry`/`TranslateCoordinates` don't).
+    ///
+    /// `.reply()` blocks the calling thread on the X11 socket, so the
+    /// round-trip runs on `spawn_blocking` rather than the async executor.
+    pub async fn geometry(&self) -> Result<X11Geometry, EvalError> {
+        let window = self.get_x_window_id().await?;
+        let conn = self.tag.screen.instance.x11()?;
+
+        return async_std::task::spawn_blocking(move || {
+            let geometry = conn.get_geometry(window).map_err(x11_err)?.reply().map_err(x11_err)?;
+            let absolute = conn
+                .translate_coordinates(window, geometry.root, 0, 0)
+                .map_err(x11_err)?
+                .reply()
+                .map_err(x11_err)?;
+
+            return Ok(X11Geometry {
+                x: absolute.dst_x,
+                y: absolute.dst_y,
+                width: geometry.width,
+                height: geometry.height,
+            });
+        })
+        .await;
+    }
+
+    /// Raw value of the X11 property named by `atom` on this client's
+    /// window (e.g. `AtomEnum::WM_NAME.into()`).
+    ///
+    /// `.reply()` blocks the calling thread on the X11 socket, so the
+    /// round-trip runs on `spawn_blocking` rather than the async executor.
+    pub async fn get_property(&self, atom: u32) -> Result<Vec<u8>, EvalError> {
+        let window = self.get_x_window_id().await?;
+        let conn = self.tag.screen.instance.x11()?;
+
+        return async_std::task::spawn_blocking(move || {
+            let reply = conn
+                .get_property(false, window, atom, AtomEnum::ANY, 0, u32::MAX)
+                .map_err(x11_err)?
+                .reply()
+                .map_err(x11_err)?;
+
+            return Ok(reply.value);
+        })
+        .await;
+    }
+
+    /// This client's ICCCM `WM_NAME`, read directly from X11. `WM_NAME` is
+    /// classically `STRING` (Latin-1), not UTF-8, so a plain non-ASCII
+    /// title is decoded lossily rather than failing the whole call.
+    pub async fn get_wm_name(&self) -> Result<String, EvalError> {
+        let raw = self.get_property(u32::from(AtomEnum::WM_NAME)).await?;
+        return Ok(decode_wm_name(&raw));
+    }
+}
+
+/// Lossily decodes a `WM_NAME` property's raw bytes. Factored out of
+/// `AwesomeClient::get_wm_name` so the non-UTF-8 handling is unit-testable
+/// without a live X11 connection.
+fn decode_wm_name(raw: &[u8]) -> String {
+    return String::from_utf8_lossy(raw).into_owned();
+}
+
+/// Absolute (root-relative) window geometry, as reported by X11.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct X11Geometry {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Accumulates Lua expressions to resolve with a single `eval` call.
+///
+/// Every getter on `Awesome`/`AwesomeScreen`/`AwesomeTag`/`AwesomeClient`
+/// fires its own `eval`, so walking screens -> tags -> clients costs one
+/// D-Bus round-trip per property. `BatchQuery` instead queues up
+/// expressions and resolves all of them in one shot.
+pub struct BatchQuery<'a> {
+    awesome: &'a Awesome<'a>,
+    exprs: Vec<String>,
+}
+
+impl<'a> BatchQuery<'a> {
+    fn new(awesome: &'a Awesome<'a>) -> Self {
+        return BatchQuery {
+            awesome,
+            exprs: Vec::new(),
+        };
+    }
+
+    /// Queues a Lua expression to be resolved on the next `resolve()`.
+    pub fn push(&mut self, expr: impl Into<String>) -> &mut Self {
+        self.exprs.push(expr.into());
+        return self;
+    }
+
+    /// Resolves every queued expression with a single `eval` call,
+    /// returning one result per expression in push order. An expression
+    /// that evaluates to `nil` (or raises a Lua error, e.g. indexing a
+    /// client that vanished since it was enumerated) resolves to `None`
+    /// instead of failing the whole batch. The separator bytes `\x01` and
+    /// `\x02` are stripped from every value, so no value can split into
+    /// extra results.
+    pub async fn resolve(&self) -> Result<Vec<Option<String>>, EvalError> {
+        const FIELD_SEP: &str = "\u{1}";
+        const NIL: &str = "\u{2}";
+
+        if self.exprs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let wrapped: Vec<String> = self
+            .exprs
+            .iter()
+            .map(|expr| {
+                format!(
+                    "(function() local ok, v = pcall(function() return {} end) \
+                     if ok and v ~= nil then return (tostring(v):gsub(\"[{}{}]\", \"\")) \
+                     else return \"{}\" end end)()",
+                    expr, FIELD_SEP, NIL, NIL
+                )
+            })
+            .collect();
+
+        let script = format!(
+            "return table.concat({{{}}}, \"{}\")",
+            wrapped.join(", "),
+            FIELD_SEP
+        );
+        let raw = self.awesome.execute_script(&script).await?;
+
+        return Ok(raw
+            .split(FIELD_SEP)
+            .map(|field| if field == NIL { None } else { Some(field.to_string()) })
+            .collect());
+    }
+}
+
+/// A client as returned by `Awesome::get_full_tree`.
+#[derive(Debug, Clone)]
+pub struct FullClient {
+    pub index: u32,
+    pub name: Option<String>,
+    pub class: Option<String>,
+    pub window: Option<u32>,
+}
+
+/// A tag as returned by `Awesome::get_full_tree`.
+#[derive(Debug, Clone)]
+pub struct FullTag {
+    pub index: u32,
+    pub name: Option<String>,
+    pub clients: Vec<FullClient>,
+}
+
+/// A screen as returned by `Awesome::get_full_tree`.
+#[derive(Debug, Clone)]
+pub struct FullScreen {
+    pub index: u32,
+    pub tags: Vec<FullTag>,
+}
+
+/// Builds the whole screen/tag/client tree inside AwesomeWM itself, then
+/// serializes it to a single delimited string, so `get_full_tree` can
+/// fetch it with one `BatchQuery` expression. The inner delimiters
+/// (`\x04`/`\x05`/`\x06`) are distinct from `BatchQuery`'s own
+/// (`\x01`/`\x02`) since this whole script is itself just one queued
+/// expression. `enc` strips those delimiter bytes out of encoded values,
+/// since client/tag names are arbitrary window-supplied strings that
+/// could otherwise desync `parse_full_tree`'s column count.
+const FULL_TREE_EXPR: &str = r#"(function()
+    local FSEP, RSEP, NIL = "\x04", "\x05", "\x06"
+    local function enc(v)
+        if v == nil then
+            return NIL
+        end
+        -- Client/tag names are arbitrary window-supplied strings; strip any
+        -- byte that collides with our delimiters so one can't desync
+        -- parse_full_tree's column count.
+        return (tostring(v):gsub("[\x04\x05\x06]", ""))
+    end
+    local rows = {}
+    for s = 1, screen.count() do
+        local tags = screen[s].tags
+        if #tags == 0 then
+            rows[#rows + 1] = table.concat({ s, "", "", "", "", "", "" }, FSEP)
+        else
+            for t = 1, #tags do
+                local tag = tags[t]
+                local clients = tag:clients()
+                if #clients == 0 then
+                    rows[#rows + 1] = table.concat({ s, t, enc(tag.name), "", "", "", "" }, FSEP)
+                else
+                    for c = 1, #clients do
+                        local cl = clients[c]
+                        rows[#rows + 1] =
+                            table.concat({ s, t, enc(tag.name), c, enc(cl.name), enc(cl.class), enc(cl.window) }, FSEP)
+                    end
+                end
+            end
+        end
+    end
+    return table.concat(rows, RSEP)
+end)()"#;
+
+/// Parses the row/field-delimited string produced by `FULL_TREE_EXPR`
+/// into the owned `FullScreen` tree. A row's tag index (field 1) is blank
+/// for a screen with no tags at all, and a row's client index (field 3)
+/// is blank for a tag with no clients at all; both are structural (every
+/// screen/tag is reported even when empty), distinct from a `NIL`-encoded
+/// field, which means the property read back as Lua `nil`.
+fn parse_full_tree(raw: &str) -> Result<Vec<FullScreen>, EvalError> {
+    const FIELD_SEP: char = '\u{4}';
+    const ROW_SEP: char = '\u{5}';
+    const NIL: &str = "\u{6}";
+
+    fn decode(field: &str) -> Option<String> {
+        if field == NIL {
+            None
+        } else {
+            Some(field.to_string())
+        }
+    }
+
+    let mut screens: Vec<FullScreen> = Vec::new();
+
+    for row in raw.split(ROW_SEP) {
+        if row.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = row.split(FIELD_SEP).collect();
+        if fields.len() != 7 {
+            return Err(EvalError::BatchParseError(row.to_string()));
+        }
+
+        let screen_index: u32 = fields[0]
+            .parse::<u32>()
+            .map_err(EvalError::CountParseError)?
+            - 1;
+
+        if !screens.last().map_or(false, |s| s.index == screen_index) {
+            screens.push(FullScreen {
+                index: screen_index,
+                tags: Vec::new(),
+            });
+        }
+
+        if fields[1].is_empty() {
+            // This screen has no tags at all.
+            continue;
+        }
+
+        let tag_index: u32 = fields[1]
+            .parse::<u32>()
+            .map_err(EvalError::CountParseError)?
+            - 1;
+        let tag_name = decode(fields[2]);
+
+        let screen = screens.last_mut().unwrap();
+        if !screen.tags.last().map_or(false, |t| t.index == tag_index) {
+            screen.tags.push(FullTag {
+                index: tag_index,
+                name: tag_name,
+                clients: Vec::new(),
+            });
+        }
+        let tag = screen.tags.last_mut().unwrap();
+
+        if fields[3].is_empty() {
+            // This tag has no clients at all.
+            continue;
+        }
+
+        let client_index: u32 = fields[3]
+            .parse::<u32>()
+            .map_err(EvalError::CountParseError)?
+            - 1;
+        tag.clients.push(FullClient {
+            index: client_index,
+            name: decode(fields[4]),
+            class: decode(fields[5]),
+            window: fields[6].parse().ok(),
+        });
+    }
+
+    return Ok(screens);
+}
+
+/// An in-memory snapshot of the screen/tag/client tree, decoupled from the
+/// D-Bus transport that populates it.
+///
+/// `AwesomeScreen`/`AwesomeTag`/`AwesomeClient` re-query D-Bus on every
+/// navigation, and their borrow chain (`&'a Awesome` -> `&'a
+/// AwesomeScreen` -> ...) forces the whole walk to live on one call's
+/// stack. `ScreenRegistry` instead snapshots the full tree into
+/// `Arc`-shared nodes callers can hold across awaits, and only re-hits
+/// D-Bus when `refresh()` is called explicitly.
+pub struct ScreenRegistry<'a> {
+    awesome: &'a Awesome<'a>,
+    screens: RwLock<Vec<Arc<ScreenNode>>>,
+}
+
+impl<'a> ScreenRegistry<'a> {
+    fn new(awesome: &'a Awesome<'a>) -> Self {
+        return ScreenRegistry {
+            awesome,
+            screens: RwLock::new(Vec::new()),
+        };
+    }
+
+    /// Re-runs the batched full-tree query and replaces the in-memory
+    /// snapshot. `Arc<ClientNode>` handles from the snapshot being replaced
+    /// are left in place rather than mutated; ones whose window AwesomeWM
+    /// no longer reports get flagged `stale` so callers notice rather than
+    /// silently acting on a dead handle. Handles from older snapshots are
+    /// not checked again and keep whatever flag they had.
+    pub async fn refresh(&self) -> Result<(), EvalError> {
+        // `get_full_tree` errors out rather than reporting an empty tree
+        // when the script behind it fails, so a transient Lua hiccup
+        // can't reach `mark_stale_clients` below and flag every
+        // previously-held handle stale.
+        let tree = self.awesome.get_full_tree().await?;
+
+        let live_windows: HashSet<u32> = tree
+            .iter()
+            .flat_map(|screen| &screen.tags)
+            .flat_map(|tag| &tag.clients)
+            .filter_map(|client| client.window)
+            .collect();
+
+        mark_stale_clients(&self.get_clients(), &live_windows);
+
+        let screens = tree.into_iter().map(ScreenNode::from_full).collect();
+        *self.screens.write().unwrap() = screens;
+
+        return Ok(());
+    }
+
+    /// Screens as of the last `refresh()`.
+    pub fn get_screens(&self) -> Vec<Arc<ScreenNode>> {
+        return self.screens.read().unwrap().clone();
+    }
+
+    /// Every client across every screen/tag, as of the last `refresh()`.
+    fn get_clients(&self) -> Vec<Arc<ClientNode>> {
+        return self
+            .get_screens()
+            .iter()
+            .flat_map(|screen| screen.tags.clone())
+            .flat_map(|tag| tag.get_clients())
+            .collect();
+    }
+}
+
+/// Flags every client in `clients` whose window isn't in `live_windows` as
+/// stale. Factored out of `refresh()` as a pure function so the set-diff
+/// itself is unit-testable without a live D-Bus connection.
+fn mark_stale_clients(clients: &[Arc<ClientNode>], live_windows: &HashSet<u32>) {
+    for client in clients {
+        if client.window.map_or(false, |window| !live_windows.contains(&window)) {
+            client.stale.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A screen as snapshotted by `ScreenRegistry`.
+#[derive(Debug)]
+pub struct ScreenNode {
+    pub index: u32,
+    pub tags: Vec<Arc<TagNode>>,
+}
+
+impl ScreenNode {
+    fn from_full(screen: FullScreen) -> Arc<Self> {
+        return Arc::new(ScreenNode {
+            index: screen.index,
+            tags: screen.tags.into_iter().map(TagNode::from_full).collect(),
+        });
+    }
+
+    /// Re-associates this snapshot with a live `AwesomeScreen` handle, so
+    /// callers can navigate further (`get_tag_count`, ...) against it.
+    pub fn as_live<'a>(&self, awesome: &'a Awesome<'a>) -> AwesomeScreen<'a> {
+        return AwesomeScreen { index: self.index, instance: awesome };
+    }
+
+    pub fn get_tags(&self) -> Vec<Arc<TagNode>> {
+        return self.tags.clone();
+    }
+}
+
+/// A tag as snapshotted by `ScreenRegistry`.
+#[derive(Debug)]
+pub struct TagNode {
+    pub index: u32,
+    pub name: Option<String>,
+    clients: Vec<Arc<ClientNode>>,
+}
+
+impl TagNode {
+    fn from_full(tag: FullTag) -> Arc<Self> {
+        return Arc::new(TagNode {
+            index: tag.index,
+            name: tag.name,
+            clients: tag.clients.into_iter().map(ClientNode::from_full).collect(),
+        });
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        return self.name.as_deref();
+    }
+
+    /// A whole `TagNode` is replaced wholesale by `ScreenRegistry::refresh`,
+    /// so `clients` is never mutated in place and needs no lock.
+    pub fn get_clients(&self) -> Vec<Arc<ClientNode>> {
+        return self.clients.clone();
+    }
+
+    /// Re-associates this snapshot with a live `AwesomeTag` handle, so
+    /// callers can act on it (`view_only`, `toggle`, ...).
+    pub fn as_live<'a>(&self, screen: &'a AwesomeScreen<'a>) -> AwesomeTag<'a> {
+        return AwesomeTag { index: self.index, screen };
+    }
+}
+
+/// A client as snapshotted by `ScreenRegistry`.
+#[derive(Debug)]
+pub struct ClientNode {
+    pub index: u32,
+    pub name: Option<String>,
+    pub class: Option<String>,
+    pub window: Option<u32>,
+    stale: AtomicBool,
+}
+
+impl ClientNode {
+    fn from_full(client: FullClient) -> Arc<Self> {
+        return Arc::new(ClientNode {
+            index: client.index,
+            name: client.name,
+            class: client.class,
+            window: client.window,
+            stale: AtomicBool::new(false),
+        });
+    }
+
+    /// Whether AwesomeWM reported this client gone as of the last
+    /// `refresh()` (e.g. it was killed).
+    pub fn is_stale(&self) -> bool {
+        return self.stale.load(Ordering::Relaxed);
+    }
+
+    /// Re-associates this snapshot with a live `AwesomeClient` handle, so
+    /// callers can issue further commands (`kill`, `move_to_tag`, ...)
+    /// against it. Returns `EvalError::StaleNode` instead of a handle that
+    /// would query `tag`'s client list at what may now be a different
+    /// client's index.
+    pub fn as_live<'a>(&self, tag: &'a AwesomeTag<'a>) -> Result<AwesomeClient<'a>, EvalError> {
+        if self.is_stale() {
+            return Err(EvalError::StaleNode);
+        }
+        return Ok(AwesomeClient { index: self.index, tag });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> String {
+        return fields.join("\u{4}");
+    }
+
+    #[test]
+    fn parses_screen_tag_and_client() {
+        let raw = row(&["1", "1", "main", "1", "foo", "Bar", "42"]);
+        let screens = parse_full_tree(&raw).unwrap();
+
+        assert_eq!(screens.len(), 1);
+        assert_eq!(screens[0].index, 0);
+        assert_eq!(screens[0].tags.len(), 1);
+        assert_eq!(screens[0].tags[0].index, 0);
+        assert_eq!(screens[0].tags[0].name.as_deref(), Some("main"));
+        assert_eq!(screens[0].tags[0].clients.len(), 1);
+
+        let client = &screens[0].tags[0].clients[0];
+        assert_eq!(client.index, 0);
+        assert_eq!(client.name.as_deref(), Some("foo"));
+        assert_eq!(client.class.as_deref(), Some("Bar"));
+        assert_eq!(client.window, Some(42));
+    }
+
+    #[test]
+    fn reports_a_screen_with_no_tags() {
+        let raw = row(&["2", "", "", "", "", "", ""]);
+        let screens = parse_full_tree(&raw).unwrap();
+
+        assert_eq!(screens.len(), 1);
+        assert_eq!(screens[0].index, 1);
+        assert!(screens[0].tags.is_empty());
+    }
+
+    #[test]
+    fn reports_a_tag_with_no_clients() {
+        let raw = row(&["1", "2", "empty", "", "", "", ""]);
+        let screens = parse_full_tree(&raw).unwrap();
+
+        assert_eq!(screens[0].tags.len(), 1);
+        assert_eq!(screens[0].tags[0].name.as_deref(), Some("empty"));
+        assert!(screens[0].tags[0].clients.is_empty());
+    }
+
+    #[test]
+    fn decodes_nil_sentinel_fields_as_none() {
+        let raw = row(&["1", "1", "\u{6}", "1", "\u{6}", "\u{6}", "\u{6}"]);
+        let screens = parse_full_tree(&raw).unwrap();
+
+        let tag = &screens[0].tags[0];
+        assert_eq!(tag.name, None);
+
+        let client = &tag.clients[0];
+        assert_eq!(client.name, None);
+        assert_eq!(client.class, None);
+        assert_eq!(client.window, None);
+    }
+
+    #[test]
+    fn rejects_a_malformed_row() {
+        let raw = row(&["1", "1", "main"]);
+        let err = parse_full_tree(&raw).unwrap_err();
+
+        assert!(matches!(err, EvalError::BatchParseError(_)));
+    }
+
+    /// Inverts `quote_lua_string` well enough to round-trip it in tests,
+    /// mirroring Lua's own long-bracket semantics (the string ends at the
+    /// first closing bracket with the opening's `=` count; a newline right
+    /// after the opening bracket is dropped).
+    fn unquote_long_bracket(quoted: &str) -> &str {
+        let eq_len = quoted[1..].bytes().take_while(|&b| b == b'=').count();
+        let closing = format!("]{}]", "=".repeat(eq_len));
+        let rest = &quoted[2 + eq_len..];
+        let body = &rest[..rest.find(&closing).unwrap()];
+        return body.strip_prefix('\n').unwrap_or(body);
+    }
+
+    #[test]
+    fn quote_lua_string_round_trips_bracket_sequences() {
+        for payload in [
+            "simple",
+            "has \"quotes\" and 'ticks'",
+            "]]",
+            "]=]",
+            "]==]",
+            "multi\nline",
+        ] {
+            let quoted = quote_lua_string(payload);
+            assert_eq!(unquote_long_bracket(&quoted), payload);
+        }
+    }
+
+    #[test]
+    fn lua_path_applies_the_one_based_conversion_once() {
+        let expr = LuaPath::screen(0).tag(1).client(2).field("class").into_expr();
+        assert_eq!(expr, "screen[1].tags[2]:clients()[3].class");
+    }
+
+    #[test]
+    fn lua_path_call_renders_args_through_render() {
+        let expr = LuaPath::screen(0).call("move_to_tag", &[LuaVal::Int(5)]).into_expr();
+        assert_eq!(expr, "screen[1]:move_to_tag(5)");
+    }
+
+    #[test]
+    fn string_args_cannot_break_out_of_the_quoted_literal() {
+        let payload = r#"x") end) awful.spawn("rm -rf ~") --"#;
+        let expr = LuaPath::screen(0)
+            .call("move_to_tag", &[LuaVal::Str(payload.to_string())])
+            .into_expr();
+
+        // Wrapped as a single long-bracket string literal, not spliced in
+        // with `"`-style quoting the payload could escape.
+        assert!(expr.starts_with("screen[1]:move_to_tag([["));
+        assert!(expr.contains(payload));
+    }
+
+    #[test]
+    fn spawn_expr_quotes_the_command_through_a_long_bracket() {
+        let payload = r#"foo") end) os.execute("rm -rf ~") --"#;
+        let expr = spawn_expr(payload);
+
+        assert!(expr.starts_with("awful.spawn([["));
+        assert!(expr.contains(payload));
+    }
+
+    #[test]
+    fn events_hook_script_is_guarded_against_double_install() {
+        assert!(EVENTS_HOOK_SCRIPT.contains("if not _G.__awmrandr_events_hooked then"));
+        assert!(EVENTS_HOOK_SCRIPT.contains("_G.__awmrandr_events_hooked = true"));
+    }
+
+    #[test]
+    fn events_hook_script_emits_each_wire_name_the_events_proxy_expects() {
+        // These must match the `name = "..."` overrides on the `Events`
+        // dbus_proxy trait verbatim, or the emitter and receiver desync.
+        for name in ["client_managed", "client_unmanaged", "client_focused", "tag_selected"] {
+            assert!(
+                EVENTS_HOOK_SCRIPT.contains(&format!("emit(\"{}\"", name)),
+                "hook script never emits {name}"
+            );
+        }
+
+        // `emit_signal`'s first argument selects the bus; it must name a
+        // bus ("session"/"system"), not the D-Bus interface.
+        assert!(EVENTS_HOOK_SCRIPT.contains(r#"dbus.emit_signal("session", "/", "org.awesomewm.awful.Events", name, ...)"#));
+    }
+
+    #[test]
+    fn decode_wm_name_is_lossy_instead_of_failing_on_latin1() {
+        // 0xE9 is Latin-1 'é', not valid UTF-8 on its own.
+        let raw = [b'c', b'a', b'f', 0xE9];
+        assert_eq!(decode_wm_name(&raw), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn decode_wm_name_passes_through_valid_utf8() {
+        assert_eq!(decode_wm_name("caf\u{e9}".as_bytes()), "caf\u{e9}");
+    }
+
+    fn client_node(index: u32, window: Option<u32>) -> Arc<ClientNode> {
+        return ClientNode::from_full(FullClient {
+            index,
+            name: None,
+            class: None,
+            window,
+        });
+    }
+
+    #[test]
+    fn mark_stale_clients_flags_only_windows_missing_from_the_live_set() {
+        let still_alive = client_node(0, Some(1));
+        let killed = client_node(1, Some(2));
+        let never_had_a_window = client_node(2, None);
+        let clients = vec![still_alive.clone(), killed.clone(), never_had_a_window.clone()];
+
+        let live_windows: HashSet<u32> = [1].into_iter().collect();
+        mark_stale_clients(&clients, &live_windows);
+
+        assert!(!still_alive.is_stale());
+        assert!(killed.is_stale());
+        assert!(!never_had_a_window.is_stale());
+    }
 }
@@ -10,19 +10,18 @@ async fn main() -> Result<()> {
     let connection = Connection::session().await?;
     let awesome = Awesome::new(&connection).await?;
 
-    let screens = awesome.get_screens().await?;
+    // A single `eval` round-trip, rather than one per property.
+    let screens = awesome.get_full_tree().await?;
     for screen in screens {
-        let tag_count = screen.get_tag_count().await?;
-
-        println!("There are {} tags on screen {}", tag_count, screen.index);
-
-        let tags = screen.get_tags().await?;
-
-        for tag in tags {
-            let clients = tag.get_clients().await?;
-
-            for client in clients {
-                println!("Client {} on tag {}", client.get_class().await?, tag.index);
+        println!("There are {} tags on screen {}", screen.tags.len(), screen.index);
+
+        for tag in screen.tags {
+            for client in tag.clients {
+                println!(
+                    "Client {} on tag {}",
+                    client.class.as_deref().unwrap_or("<unknown>"),
+                    tag.index
+                );
             }
         }
     }